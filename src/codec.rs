@@ -0,0 +1,42 @@
+use crate::protocol::{self, RespParseError, RespValue};
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::io;
+
+/// Frames a byte stream into `RespValue`s using `protocol::parse`.
+///
+/// Unlike the original hand-rolled read loop, the decoder only drops the
+/// prefix it actually consumed (`buf.advance(consumed)`), so pipelined
+/// commands don't force a copy of the whole pending buffer on every frame.
+pub struct RespCodec;
+
+impl Decoder for RespCodec {
+    type Item = RespValue;
+    type Error = io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match protocol::parse(buf) {
+            Ok((value, consumed)) => {
+                buf.advance(consumed);
+                Ok(Some(value))
+            }
+            Err(RespParseError::Incomplete) => Ok(None),
+            Err(RespParseError::InvalidFormat) => {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "RESP parse error"))
+            }
+        }
+    }
+}
+
+impl Encoder<RespValue> for RespCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: RespValue, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut buf = Vec::new();
+        protocol::encode(&item, &mut buf);
+        dst.extend_from_slice(&buf);
+        Ok(())
+    }
+}
@@ -2,22 +2,80 @@ use crate::protocol::{RespValue, RespParseError};
 
 #[derive(Debug)]
 pub enum Command {
-    Ping(Option<String>),
-    Echo(String),
-    Get(String),
+    Ping(Option<Vec<u8>>),
+    Echo(Vec<u8>),
+    Get(Vec<u8>),
     Set {
-        key: String,
-        value: String,
+        key: Vec<u8>,
+        value: Vec<u8>,
         px: Option<u64>,
     },
+    Hello {
+        proto: Option<u8>,
+    },
+    Subscribe(Vec<String>),
+    Unsubscribe(Vec<String>),
+    Publish {
+        channel: String,
+        message: Vec<u8>,
+    },
+    Expire {
+        key: Vec<u8>,
+        seconds: i64,
+    },
+    Pexpire {
+        key: Vec<u8>,
+        ms: i64,
+    },
+    Ttl(Vec<u8>),
+    Pttl(Vec<u8>),
+    Persist(Vec<u8>),
     Unknown(String),
 }
 
+/// Parses the `key` / numeric-argument pair shared by `EXPIRE` and `PEXPIRE`.
+fn key_and_amount(items: &[RespValue]) -> Result<(Vec<u8>, i64), RespParseError> {
+    let key = match items.get(1) {
+        Some(RespValue::BulkString(Some(k))) => k.clone(),
+        _ => return Err(RespParseError::InvalidFormat),
+    };
+    let amount = match items.get(2) {
+        Some(RespValue::BulkString(Some(n))) => std::str::from_utf8(n)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or(RespParseError::InvalidFormat)?,
+        _ => return Err(RespParseError::InvalidFormat),
+    };
+    Ok((key, amount))
+}
+
+/// Parses the single `key` argument shared by `TTL`, `PTTL` and `PERSIST`.
+fn single_key(items: &[RespValue]) -> Result<Vec<u8>, RespParseError> {
+    match items.get(1) {
+        Some(RespValue::BulkString(Some(key))) => Ok(key.clone()),
+        _ => Err(RespParseError::InvalidFormat),
+    }
+}
+
+/// Collects the bulk-string arguments in `items[1..]` as UTF-8 channel names,
+/// lossily decoding any non-UTF-8 bytes.
+fn channel_names(items: &[RespValue]) -> Result<Vec<String>, RespParseError> {
+    items[1..]
+        .iter()
+        .map(|v| match v {
+            RespValue::BulkString(Some(s)) => Ok(String::from_utf8_lossy(s).into_owned()),
+            _ => Err(RespParseError::InvalidFormat),
+        })
+        .collect()
+}
+
 pub fn parse_command(value: RespValue) -> Result<Command, RespParseError> {
     match value {
         RespValue::Array(items) => {
             let cmd = match items.get(0) {
-                Some(RespValue::BulkString(Some(s))) => s.to_uppercase(),
+                Some(RespValue::BulkString(Some(s))) => {
+                    String::from_utf8_lossy(s).to_uppercase()
+                }
                 _ => return Err(RespParseError::InvalidFormat),
             };
 
@@ -60,14 +118,61 @@ pub fn parse_command(value: RespValue) -> Result<Command, RespParseError> {
                             Some(RespValue::BulkString(Some(ms))),
                         ) = (items.get(3), items.get(4))
                         {
-                            if opt.to_uppercase() == "PX" {
-                                px = ms.parse::<u64>().ok();
+                            if opt.eq_ignore_ascii_case(b"PX") {
+                                px = std::str::from_utf8(ms)
+                                    .ok()
+                                    .and_then(|s| s.parse::<u64>().ok());
                             }
                         }
                     }
 
                     Ok(Command::Set { key, value, px })
                 }
+                "HELLO" => {
+                    let proto = match items.get(1) {
+                        Some(RespValue::BulkString(Some(s))) => Some(
+                            std::str::from_utf8(s)
+                                .ok()
+                                .and_then(|s| s.parse::<u8>().ok())
+                                .ok_or(RespParseError::InvalidFormat)?,
+                        ),
+                        None => None,
+                        _ => return Err(RespParseError::InvalidFormat),
+                    };
+                    Ok(Command::Hello { proto })
+                }
+                "SUBSCRIBE" => {
+                    let channels = channel_names(&items)?;
+                    if channels.is_empty() {
+                        return Err(RespParseError::InvalidFormat);
+                    }
+                    Ok(Command::Subscribe(channels))
+                }
+                "UNSUBSCRIBE" => Ok(Command::Unsubscribe(channel_names(&items)?)),
+                "PUBLISH" => {
+                    let channel = match items.get(1) {
+                        Some(RespValue::BulkString(Some(c))) => {
+                            String::from_utf8_lossy(c).into_owned()
+                        }
+                        _ => return Err(RespParseError::InvalidFormat),
+                    };
+                    let message = match items.get(2) {
+                        Some(RespValue::BulkString(Some(m))) => m.clone(),
+                        _ => return Err(RespParseError::InvalidFormat),
+                    };
+                    Ok(Command::Publish { channel, message })
+                }
+                "EXPIRE" => {
+                    let (key, seconds) = key_and_amount(&items)?;
+                    Ok(Command::Expire { key, seconds })
+                }
+                "PEXPIRE" => {
+                    let (key, ms) = key_and_amount(&items)?;
+                    Ok(Command::Pexpire { key, ms })
+                }
+                "TTL" => Ok(Command::Ttl(single_key(&items)?)),
+                "PTTL" => Ok(Command::Pttl(single_key(&items)?)),
+                "PERSIST" => Ok(Command::Persist(single_key(&items)?)),
                 other => Ok(Command::Unknown(other.to_string())),
             }
         }
@@ -1,120 +1,446 @@
 mod protocol;
 mod command;
+mod codec;
 
-use protocol::parse;
 use command::parse_command;
 use command::Command;
+use codec::RespCodec;
+use protocol::RespValue;
 
 use tokio::net::TcpListener;
-use tokio::io::AsyncReadExt;
-use tokio::io::AsyncWriteExt;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
+use tokio_util::codec::Framed;
+use futures::{SinkExt, StreamExt};
+use rand::seq::SliceRandom;
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::Arc;
-use std::time::Duration; 
+use std::time::Duration;
 use std::time::Instant;
 
 #[derive(Clone)]
 struct ValueEntry {
-    value: String,
+    value: Vec<u8>,
     expires_at: Option<Instant>,
 }
 
+type Db = Arc<Mutex<HashMap<Vec<u8>, ValueEntry>>>;
+
+/// A subscriber: the sender that forwards into its connection's outbound
+/// stream, paired with that same connection's negotiated protocol version
+/// so a publish can pick RESP2 vs RESP3 framing per recipient.
+type Subscriber = (Arc<AtomicU8>, mpsc::UnboundedSender<RespValue>);
+
+/// Per-channel list of subscribers. Published messages fan out to every
+/// sender registered under the channel name; each sender forwards into its
+/// own connection's outbound stream.
+type PubSub = Arc<Mutex<HashMap<String, Vec<Subscriber>>>>;
+
+/// How often the active-expiry sweeper wakes up to sample the keyspace.
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_millis(100);
+/// How many keys the sweeper samples per pass.
+const EXPIRY_SAMPLE_SIZE: usize = 20;
+/// If more than this fraction of a sample was expired, sweep again
+/// immediately instead of waiting for the next tick.
+const EXPIRY_SWEEP_THRESHOLD: f64 = 0.25;
+
 #[tokio::main]
 async fn main() {
     let listener = TcpListener::bind("127.0.0.1:6379").await.unwrap();
-    let db = Arc::new(Mutex::new(HashMap::<String, ValueEntry>::new()));
+    let db: Db = Arc::new(Mutex::new(HashMap::new()));
+    let pubsub: PubSub = Arc::new(Mutex::new(HashMap::new()));
+
+    spawn_expiry_sweeper(db.clone());
 
     loop {
-        let (mut stream, _) = listener.accept().await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
         let db = db.clone();
+        let pubsub = pubsub.clone();
 
         tokio::spawn(async move {
-            let mut buffer = Vec::new();
-            let mut read_buf = [0u8; 512];
+            let mut framed = Framed::new(stream, RespCodec);
+            // RESP2 until the client opts into RESP3 via `HELLO 3`. Shared
+            // via `Arc` so a publisher on another connection can read this
+            // connection's negotiated version when framing a pub/sub message.
+            let proto_version = Arc::new(AtomicU8::new(2));
+            let mut subscriptions: Vec<String> = Vec::new();
+            let (tx, mut rx) = mpsc::unbounded_channel::<RespValue>();
 
             loop {
-                match stream.read(&mut read_buf).await {
-                    Ok(0) => break,
-                    Ok(n) => {
-                        buffer.extend_from_slice(&read_buf[..n]);
-
-                        loop {
-                            let input = match std::str::from_utf8(&buffer) {
-                                Ok(s) => s,
-                                Err(_) => break,
-                            };
-
-                            match parse(input) {
-                                Ok((value, remaining)) => {
-                                    buffer = remaining.as_bytes().to_vec();
-
-                                    match parse_command(value) {
-                                        Ok(cmd) => {
-                                            handle_command(cmd, &db, &mut stream).await;
-                                        }
-                                        Err(_) => {
-                                            let _ = stream.write_all(b"-ERR invalid command\r\n").await;
-                                        }
-                                    }
-                                }
-                                Err(protocol::RespParseError::Incomplete) => break,
-                                Err(_) => {
-                                    let _ = stream.write_all(b"-ERR parse error\r\n").await;
-                                    break;
+                tokio::select! {
+                    result = framed.next() => {
+                        let Some(result) = result else { break };
+
+                        let replies = match result {
+                            Ok(value) => match parse_command(value) {
+                                Ok(cmd) => {
+                                    handle_command(
+                                        cmd,
+                                        &db,
+                                        &pubsub,
+                                        &tx,
+                                        &mut subscriptions,
+                                        &proto_version,
+                                    )
+                                    .await
                                 }
+                                Err(_) => vec![RespValue::Error("ERR invalid command".to_string())],
+                            },
+                            Err(_) => vec![RespValue::Error("ERR parse error".to_string())],
+                        };
+
+                        let mut failed = false;
+                        for reply in replies {
+                            if framed.send(reply).await.is_err() {
+                                failed = true;
+                                break;
                             }
                         }
+                        if failed {
+                            break;
+                        }
+                    }
+                    Some(message) = rx.recv() => {
+                        if framed.send(message).await.is_err() {
+                            break;
+                        }
                     }
-                    Err(_) => break,
                 }
             }
+
+            unsubscribe_all(&pubsub, &tx, &subscriptions).await;
         });
     }
 }
 
-async fn handle_command(
-    cmd: Command,
-    db: &Arc<Mutex<HashMap<String, ValueEntry>>>,
-    stream: &mut tokio::net::TcpStream,
-) {
-    match cmd {
-        Command::Ping(Some(msg)) => {
-            let _ = stream.write_all(format!("+{}\r\n", msg).as_bytes()).await;
+fn is_expired(entry: &ValueEntry) -> bool {
+    entry.expires_at.is_some_and(|at| Instant::now() > at)
+}
+
+/// Drops `key` from `db` if its entry has expired, and reports whether the
+/// key is now absent (either it never existed or was just swept away) so
+/// every command that reads or mutates `expires_at` treats an expired key
+/// the same way `Get` has always done.
+fn evict_if_expired(db: &mut HashMap<Vec<u8>, ValueEntry>, key: &[u8]) -> bool {
+    match db.get(key) {
+        Some(entry) if is_expired(entry) => {
+            db.remove(key);
+            true
+        }
+        Some(_) => false,
+        None => true,
+    }
+}
+
+/// Computes the `Instant` an `EXPIRE`/`PEXPIRE` duration resolves to, or
+/// `None` if adding it to "now" would overflow `Instant`'s range (e.g. an
+/// attacker-supplied expire time near `i64::MAX` seconds/ms out).
+fn checked_expiry(duration: Duration) -> Option<Instant> {
+    Instant::now().checked_add(duration)
+}
+
+/// Returns the reply for a missing or expired key, using the RESP3 `Null`
+/// type once the connection has negotiated protocol 3 via `HELLO`.
+fn nil_reply(proto_version: u8) -> RespValue {
+    if proto_version >= 3 {
+        RespValue::Null
+    } else {
+        RespValue::BulkString(None)
+    }
+}
+
+/// Builds a subscribe/unsubscribe acknowledgement, using the RESP3 `Push`
+/// type (instead of a plain `Array`) once the connection has negotiated
+/// protocol 3 via `HELLO` — `Push` exists precisely for this kind of
+/// out-of-band pub/sub notification.
+fn subscribe_ack(kind: &str, channel: &str, count: usize, proto_version: u8) -> RespValue {
+    let items = vec![
+        RespValue::BulkString(Some(kind.as_bytes().to_vec())),
+        RespValue::BulkString(Some(channel.as_bytes().to_vec())),
+        RespValue::Integer(count as i64),
+    ];
+    if proto_version >= 3 {
+        RespValue::Push(items)
+    } else {
+        RespValue::Array(items)
+    }
+}
+
+/// Drops `tx` from every channel it is registered under, removing the
+/// channel entry entirely once its subscriber list is empty. Called when a
+/// connection disconnects so it doesn't linger as a dead subscriber.
+async fn unsubscribe_all(pubsub: &PubSub, tx: &mpsc::UnboundedSender<RespValue>, channels: &[String]) {
+    let mut pubsub = pubsub.lock().await;
+    for channel in channels {
+        if let Some(senders) = pubsub.get_mut(channel) {
+            senders.retain(|(_, s)| !s.same_channel(tx));
+            if senders.is_empty() {
+                pubsub.remove(channel);
+            }
         }
-        Command::Ping(None) => {
-            let _ = stream.write_all(b"+PONG\r\n").await;
+    }
+}
+
+/// Spawns the background active-expiry task. On each tick it samples a
+/// handful of keys and deletes the expired ones; if more than
+/// `EXPIRY_SWEEP_THRESHOLD` of the sample was expired it sweeps again right
+/// away, Redis-style, so a burst of expirations doesn't wait for the next
+/// tick. Only the sampled keys are checked for expiry, but drawing the
+/// sample copies every key out of the map on each pass.
+fn spawn_expiry_sweeper(db: Db) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EXPIRY_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            loop {
+                let expired_ratio = sweep_expired_keys(&db).await;
+                if expired_ratio <= EXPIRY_SWEEP_THRESHOLD {
+                    break;
+                }
+            }
         }
-        Command::Echo(msg) => {
-            let _ = stream.write_all(format!("${}\r\n{}\r\n", msg.len(), msg).as_bytes()).await;
+    });
+}
+
+/// Samples up to `EXPIRY_SAMPLE_SIZE` keys and removes the expired ones,
+/// returning the fraction of the sample that was expired.
+async fn sweep_expired_keys(db: &Db) -> f64 {
+    let mut db = db.lock().await;
+    if db.is_empty() {
+        return 0.0;
+    }
+
+    let keys: Vec<Vec<u8>> = db.keys().cloned().collect();
+    let sample_size = EXPIRY_SAMPLE_SIZE.min(keys.len());
+    let sample: Vec<&Vec<u8>> = keys.choose_multiple(&mut rand::thread_rng(), sample_size).collect();
+
+    let now = Instant::now();
+    let mut expired = 0;
+    for key in &sample {
+        let is_expired = db
+            .get(*key)
+            .and_then(|entry| entry.expires_at)
+            .is_some_and(|at| now > at);
+        if is_expired {
+            db.remove(*key);
+            expired += 1;
         }
+    }
+
+    expired as f64 / sample.len() as f64
+}
+
+async fn handle_command(
+    cmd: Command,
+    db: &Db,
+    pubsub: &PubSub,
+    tx: &mpsc::UnboundedSender<RespValue>,
+    subscriptions: &mut Vec<String>,
+    proto_version: &Arc<AtomicU8>,
+) -> Vec<RespValue> {
+    match cmd {
+        Command::Ping(Some(msg)) => vec![RespValue::BulkString(Some(msg))],
+        Command::Ping(None) => vec![RespValue::SimpleString("PONG".to_string())],
+        Command::Echo(msg) => vec![RespValue::BulkString(Some(msg))],
         Command::Set { key, value, px } => {
             let expires_at = px.map(|ms| Instant::now() + Duration::from_millis(ms));
             let mut db = db.lock().await;
             db.insert(key, ValueEntry { value, expires_at });
-            let _ = stream.write_all(b"+OK\r\n").await;
+            vec![RespValue::SimpleString("OK".to_string())]
         }
         Command::Get(key) => {
             let mut db = db.lock().await;
-            if let Some(entry) = db.get(&key) {
-                if let Some(expiry) = entry.expires_at {
-                    if Instant::now() > expiry {
-                        db.remove(&key);
-                        let _ = stream.write_all(b"$-1\r\n").await;
-                        return;
+            if evict_if_expired(&mut db, &key) {
+                return vec![nil_reply(proto_version.load(Ordering::Relaxed))];
+            }
+            let entry = db.get(&key).expect("checked by evict_if_expired");
+            vec![RespValue::BulkString(Some(entry.value.clone()))]
+        }
+        Command::Hello { proto } => {
+            let version = match proto {
+                Some(v @ 2) | Some(v @ 3) => v,
+                Some(_) => {
+                    return vec![RespValue::Error(
+                        "NOPROTO unsupported protocol version".to_string(),
+                    )]
+                }
+                None => proto_version.load(Ordering::Relaxed),
+            };
+            proto_version.store(version, Ordering::Relaxed);
+
+            let fields = vec![
+                (
+                    RespValue::BulkString(Some(b"server".to_vec())),
+                    RespValue::BulkString(Some(b"tinyredis".to_vec())),
+                ),
+                (
+                    RespValue::BulkString(Some(b"version".to_vec())),
+                    RespValue::BulkString(Some(b"0.1.0".to_vec())),
+                ),
+                (
+                    RespValue::BulkString(Some(b"proto".to_vec())),
+                    RespValue::Integer(version as i64),
+                ),
+            ];
+
+            if version >= 3 {
+                vec![RespValue::Map(fields)]
+            } else {
+                vec![RespValue::Array(
+                    fields.into_iter().flat_map(|(k, v)| [k, v]).collect(),
+                )]
+            }
+        }
+        Command::Subscribe(channels) => {
+            let mut replies = Vec::with_capacity(channels.len());
+            let mut pubsub = pubsub.lock().await;
+            for channel in channels {
+                pubsub
+                    .entry(channel.clone())
+                    .or_default()
+                    .push((proto_version.clone(), tx.clone()));
+                if !subscriptions.contains(&channel) {
+                    subscriptions.push(channel.clone());
+                }
+                replies.push(subscribe_ack(
+                    "subscribe",
+                    &channel,
+                    subscriptions.len(),
+                    proto_version.load(Ordering::Relaxed),
+                ));
+            }
+            replies
+        }
+        Command::Unsubscribe(channels) => {
+            let targets = if channels.is_empty() {
+                subscriptions.clone()
+            } else {
+                channels
+            };
+
+            if targets.is_empty() {
+                let items = vec![
+                    RespValue::BulkString(Some(b"unsubscribe".to_vec())),
+                    RespValue::BulkString(None),
+                    RespValue::Integer(0),
+                ];
+                let reply = if proto_version.load(Ordering::Relaxed) >= 3 {
+                    RespValue::Push(items)
+                } else {
+                    RespValue::Array(items)
+                };
+                return vec![reply];
+            }
+
+            let mut replies = Vec::with_capacity(targets.len());
+            let mut pubsub = pubsub.lock().await;
+            for channel in targets {
+                if let Some(senders) = pubsub.get_mut(&channel) {
+                    senders.retain(|(_, s)| !s.same_channel(tx));
+                    if senders.is_empty() {
+                        pubsub.remove(&channel);
                     }
                 }
-                let response = format!("${}\r\n{}\r\n", entry.value.len(), entry.value);
-                let _ = stream.write_all(response.as_bytes()).await;
+                subscriptions.retain(|c| c != &channel);
+                replies.push(subscribe_ack(
+                    "unsubscribe",
+                    &channel,
+                    subscriptions.len(),
+                    proto_version.load(Ordering::Relaxed),
+                ));
+            }
+            replies
+        }
+        Command::Publish { channel, message } => {
+            let mut pubsub = pubsub.lock().await;
+            let delivered = if let Some(senders) = pubsub.get_mut(&channel) {
+                senders.retain(|(subscriber_proto, sender)| {
+                    let items = vec![
+                        RespValue::BulkString(Some(b"message".to_vec())),
+                        RespValue::BulkString(Some(channel.as_bytes().to_vec())),
+                        RespValue::BulkString(Some(message.clone())),
+                    ];
+                    let payload = if subscriber_proto.load(Ordering::Relaxed) >= 3 {
+                        RespValue::Push(items)
+                    } else {
+                        RespValue::Array(items)
+                    };
+                    sender.send(payload).is_ok()
+                });
+                senders.len()
+            } else {
+                0
+            };
+            vec![RespValue::Integer(delivered as i64)]
+        }
+        Command::Expire { key, seconds } => {
+            let mut db = db.lock().await;
+            if evict_if_expired(&mut db, &key) {
+                return vec![RespValue::Integer(0)];
+            }
+            let Some(expires_at) = checked_expiry(Duration::from_secs(seconds.max(0) as u64)) else {
+                return vec![RespValue::Error(
+                    "ERR invalid expire time in 'expire' command".to_string(),
+                )];
+            };
+            let entry = db.get_mut(&key).expect("checked by evict_if_expired");
+            entry.expires_at = Some(expires_at);
+            vec![RespValue::Integer(1)]
+        }
+        Command::Pexpire { key, ms } => {
+            let mut db = db.lock().await;
+            if evict_if_expired(&mut db, &key) {
+                return vec![RespValue::Integer(0)];
+            }
+            let Some(expires_at) = checked_expiry(Duration::from_millis(ms.max(0) as u64)) else {
+                return vec![RespValue::Error(
+                    "ERR invalid expire time in 'pexpire' command".to_string(),
+                )];
+            };
+            let entry = db.get_mut(&key).expect("checked by evict_if_expired");
+            entry.expires_at = Some(expires_at);
+            vec![RespValue::Integer(1)]
+        }
+        Command::Ttl(key) => {
+            let mut db = db.lock().await;
+            if evict_if_expired(&mut db, &key) {
+                return vec![RespValue::Integer(-2)];
+            }
+            let entry = db.get(&key).expect("checked by evict_if_expired");
+            vec![RespValue::Integer(match entry.expires_at {
+                None => -1,
+                Some(at) => {
+                    let remaining = at.saturating_duration_since(Instant::now());
+                    (remaining.as_millis() as i64 + 999) / 1000
+                }
+            })]
+        }
+        Command::Pttl(key) => {
+            let mut db = db.lock().await;
+            if evict_if_expired(&mut db, &key) {
+                return vec![RespValue::Integer(-2)];
+            }
+            let entry = db.get(&key).expect("checked by evict_if_expired");
+            vec![RespValue::Integer(match entry.expires_at {
+                None => -1,
+                Some(at) => at.saturating_duration_since(Instant::now()).as_millis() as i64,
+            })]
+        }
+        Command::Persist(key) => {
+            let mut db = db.lock().await;
+            if evict_if_expired(&mut db, &key) {
+                return vec![RespValue::Integer(0)];
+            }
+            let entry = db.get_mut(&key).expect("checked by evict_if_expired");
+            if entry.expires_at.take().is_some() {
+                vec![RespValue::Integer(1)]
             } else {
-                let _ = stream.write_all(b"$-1\r\n").await;
+                vec![RespValue::Integer(0)]
             }
         }
         Command::Unknown(cmd) => {
-            let msg = format!("-ERR unknown command '{}'\r\n", cmd);
-            let _ = stream.write_all(msg.as_bytes()).await;
+            vec![RespValue::Error(format!("ERR unknown command '{}'", cmd))]
         }
     }
 }
-
@@ -7,7 +7,7 @@
 /// transferred from one process to another.
 use std::str::FromStr;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum RespValue {
     /// Simple strings are encoded as a plus (+) character, followed by a string.
     /// The string mustn't contain a CR (\r) or LF (\n) character and is terminated by CRLF (i.e., \r\n).
@@ -37,7 +37,9 @@ pub enum RespValue {
     /// A final CRLF.
     /// So the string "hello" is encoded as follows:
     ///     $5\r\nhello\r\n
-    BulkString(Option<String>),
+    /// The payload is kept as raw bytes rather than a `String` so binary data (images,
+    /// serialized blobs, keys containing non-UTF-8 bytes) round-trips without validation.
+    BulkString(Option<Vec<u8>>),
     /// Clients send commands to the Redis server as RESP arrays. RESP Arrays' encoding uses the following format:
     ///     *<number-of-elements>\r\n<element-1>...<element-n>
     /// An asterisk (*) as the first byte.
@@ -47,6 +49,28 @@ pub enum RespValue {
     /// The encoding of an array consisting of the two bulk strings "hello" and "world" is:
     ///     *2\r\n$5\r\nhello\r\n$5\r\nworld\r\n
     Array(Vec<RespValue>),
+    /// RESP3 null: `_\r\n`. Replaces the RESP2 convention of a nil bulk string
+    /// or nil array for clients that have negotiated protocol 3 via `HELLO`.
+    Null,
+    /// RESP3 double: `,<floating-point-number>\r\n`.
+    Double(f64),
+    /// RESP3 boolean: `#t\r\n` or `#f\r\n`.
+    Boolean(bool),
+    /// RESP3 big number: `(<big number>\r\n`. Kept as a `String` since there is
+    /// no arbitrary-precision integer type in `std`.
+    BigNumber(String),
+    /// RESP3 verbatim string: `=<length>\r\n<3-char format>:<data>\r\n`.
+    /// `format` is the 3-character type hint (e.g. `txt`, `mkd`); `data` is the
+    /// payload that follows the colon.
+    VerbatimString { format: String, data: Vec<u8> },
+    /// RESP3 map: `%<n>\r\n` followed by `n` key/value pairs.
+    Map(Vec<(RespValue, RespValue)>),
+    /// RESP3 set: `~<n>\r\n` followed by `n` elements, like `Array` but with
+    /// set semantics on the client side.
+    Set(Vec<RespValue>),
+    /// RESP3 push: `><n>\r\n` followed by `n` elements. Used for out-of-band
+    /// messages such as pub/sub notifications.
+    Push(Vec<RespValue>),
 }
 
 #[derive(Debug)]
@@ -55,58 +79,79 @@ pub enum RespParseError {
     InvalidFormat,
 }
 
-pub fn parse(input: &str) -> Result<(RespValue, &str), RespParseError> {
-    let bytes = input.as_bytes();
+/// Returns the index of the first `\r` in `buf` whose following byte is `\n`.
+fn find_crlf(buf: &[u8]) -> Option<usize> {
+    buf.windows(2).position(|w| w == b"\r\n")
+}
+
+/// Parses one `RespValue` from the front of `input`.
+///
+/// On success, returns the parsed value together with the number of bytes
+/// consumed from `input` to produce it, so callers (e.g. a `Decoder`) can
+/// advance their buffer by exactly that amount instead of copying a remainder.
+pub fn parse(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if input.is_empty() {
+        return Err(RespParseError::Incomplete);
+    }
 
-    match bytes.first() {
-        Some(b'+') => parse_simple_string(&input[1..]),
-        Some(b'-') => parse_error(&input[1..]),
-        Some(b':') => parse_integer(&input[1..]),
-        Some(b'$') => parse_bulk_string(&input[1..]),
-        Some(b'*') => parse_array(&input[1..]),
+    match input.first() {
+        Some(b'+') => parse_simple_string(&input[1..]).map(|(v, n)| (v, n + 1)),
+        Some(b'-') => parse_error(&input[1..]).map(|(v, n)| (v, n + 1)),
+        Some(b':') => parse_integer(&input[1..]).map(|(v, n)| (v, n + 1)),
+        Some(b'$') => parse_bulk_string(&input[1..]).map(|(v, n)| (v, n + 1)),
+        Some(b'*') => parse_array(&input[1..]).map(|(v, n)| (v, n + 1)),
+        Some(b'_') => parse_null(&input[1..]).map(|(v, n)| (v, n + 1)),
+        Some(b',') => parse_double(&input[1..]).map(|(v, n)| (v, n + 1)),
+        Some(b'#') => parse_boolean(&input[1..]).map(|(v, n)| (v, n + 1)),
+        Some(b'(') => parse_big_number(&input[1..]).map(|(v, n)| (v, n + 1)),
+        Some(b'=') => parse_verbatim_string(&input[1..]).map(|(v, n)| (v, n + 1)),
+        Some(b'%') => parse_map(&input[1..]).map(|(v, n)| (v, n + 1)),
+        Some(b'~') => parse_set(&input[1..]).map(|(v, n)| (v, n + 1)),
+        Some(b'>') => parse_push(&input[1..]).map(|(v, n)| (v, n + 1)),
         _ => Err(RespParseError::InvalidFormat),
     }
 }
 
-fn parse_simple_string(input: &str) -> Result<(RespValue, &str), RespParseError> {
-    if let Some(pos) = input.find("\r\n") {
-        let val = &input[..pos];
-        let rest = &input[(pos + 2)..];
-        Ok((RespValue::SimpleString(val.to_string()), rest))
+fn parse_simple_string(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if let Some(pos) = find_crlf(input) {
+        let val = String::from_utf8(input[..pos].to_vec())
+            .map_err(|_| RespParseError::InvalidFormat)?;
+        Ok((RespValue::SimpleString(val), pos + 2))
     } else {
         Err(RespParseError::Incomplete)
     }
 }
 
-fn parse_error(input: &str) -> Result<(RespValue, &str), RespParseError> {
-    if let Some(pos) = input.find("\r\n") {
-        let val = &input[..pos];
-        let rest = &input[(pos + 2)..];
-        Ok((RespValue::Error(val.to_string()), rest))
+fn parse_error(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if let Some(pos) = find_crlf(input) {
+        let val = String::from_utf8(input[..pos].to_vec())
+            .map_err(|_| RespParseError::InvalidFormat)?;
+        Ok((RespValue::Error(val), pos + 2))
     } else {
         Err(RespParseError::Incomplete)
     }
 }
 
-fn parse_integer(input: &str) -> Result<(RespValue, &str), RespParseError> {
-    if let Some(pos) = input.find("\r\n") {
-        let num = i64::from_str(&input[..pos]).map_err(|_| RespParseError::InvalidFormat)?;
-        let rest = &input[(pos + 2)..];
-        Ok((RespValue::Integer(num), rest))
+fn parse_integer(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if let Some(pos) = find_crlf(input) {
+        let s = std::str::from_utf8(&input[..pos]).map_err(|_| RespParseError::InvalidFormat)?;
+        let num = i64::from_str(s).map_err(|_| RespParseError::InvalidFormat)?;
+        Ok((RespValue::Integer(num), pos + 2))
     } else {
         Err(RespParseError::Incomplete)
     }
 }
 
-fn parse_bulk_string(input: &str) -> Result<(RespValue, &str), RespParseError> {
-    if let Some(pos) = input.find("\r\n") {
-        let len: isize = input[..pos]
-            .parse()
-            .map_err(|_| RespParseError::InvalidFormat)?;
-        let rest = &input[(pos + 2)..];
+fn parse_bulk_string(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if let Some(pos) = find_crlf(input) {
+        let len_str =
+            std::str::from_utf8(&input[..pos]).map_err(|_| RespParseError::InvalidFormat)?;
+        let len: isize = len_str.parse().map_err(|_| RespParseError::InvalidFormat)?;
+        let header_len = pos + 2;
+        let rest = &input[header_len..];
 
         if len == -1 {
-            return Ok((RespValue::BulkString(None), rest));
+            return Ok((RespValue::BulkString(None), header_len));
         }
 
         let len = len as usize;
@@ -114,41 +159,255 @@ fn parse_bulk_string(input: &str) -> Result<(RespValue, &str), RespParseError> {
             return Err(RespParseError::Incomplete);
         }
 
-        let val = &rest[..len];
-        if &rest[len..len + 2] != "\r\n" {
+        let val = rest[..len].to_vec();
+        if &rest[len..len + 2] != b"\r\n" {
             return Err(RespParseError::InvalidFormat);
         }
 
-        Ok((
-            RespValue::BulkString(Some(val.to_string())),
-            &rest[(len + 2)..],
-        ))
+        Ok((RespValue::BulkString(Some(val)), header_len + len + 2))
     } else {
         Err(RespParseError::Incomplete)
     }
 }
 
-fn parse_array(input: &str) -> Result<(RespValue, &str), RespParseError> {
-    if let Some(pos) = input.find("\r\n") {
-        let len: isize = input[..pos]
-            .parse()
-            .map_err(|_| RespParseError::InvalidFormat)?;
-        let mut rest = &input[(pos + 2)..];
+fn parse_array(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if let Some(pos) = find_crlf(input) {
+        let len_str =
+            std::str::from_utf8(&input[..pos]).map_err(|_| RespParseError::InvalidFormat)?;
+        let len: isize = len_str.parse().map_err(|_| RespParseError::InvalidFormat)?;
+        let mut consumed = pos + 2;
 
         if len == -1 {
-            return Ok((RespValue::Array(vec![]), rest));
+            return Ok((RespValue::Array(vec![]), consumed));
         }
 
         let mut items = Vec::with_capacity(len as usize);
 
         for _ in 0..len {
-            let (val, new_rest) = parse(rest)?;
+            let (val, used) = parse(&input[consumed..])?;
             items.push(val);
-            rest = new_rest;
+            consumed += used;
+        }
+
+        Ok((RespValue::Array(items), consumed))
+    } else {
+        Err(RespParseError::Incomplete)
+    }
+}
+
+fn parse_null(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if input.len() < 2 {
+        return Err(RespParseError::Incomplete);
+    }
+    if &input[..2] == b"\r\n" {
+        Ok((RespValue::Null, 2))
+    } else {
+        Err(RespParseError::InvalidFormat)
+    }
+}
+
+fn parse_double(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if let Some(pos) = find_crlf(input) {
+        let s = std::str::from_utf8(&input[..pos]).map_err(|_| RespParseError::InvalidFormat)?;
+        let num: f64 = s.parse().map_err(|_| RespParseError::InvalidFormat)?;
+        Ok((RespValue::Double(num), pos + 2))
+    } else {
+        Err(RespParseError::Incomplete)
+    }
+}
+
+fn parse_boolean(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if input.len() < 3 {
+        return Err(RespParseError::Incomplete);
+    }
+    if &input[1..3] != b"\r\n" {
+        return Err(RespParseError::InvalidFormat);
+    }
+    match input[0] {
+        b't' => Ok((RespValue::Boolean(true), 3)),
+        b'f' => Ok((RespValue::Boolean(false), 3)),
+        _ => Err(RespParseError::InvalidFormat),
+    }
+}
+
+fn parse_big_number(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if let Some(pos) = find_crlf(input) {
+        let s = std::str::from_utf8(&input[..pos]).map_err(|_| RespParseError::InvalidFormat)?;
+        Ok((RespValue::BigNumber(s.to_string()), pos + 2))
+    } else {
+        Err(RespParseError::Incomplete)
+    }
+}
+
+fn parse_verbatim_string(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if let Some(pos) = find_crlf(input) {
+        let len_str =
+            std::str::from_utf8(&input[..pos]).map_err(|_| RespParseError::InvalidFormat)?;
+        let len: usize = len_str.parse().map_err(|_| RespParseError::InvalidFormat)?;
+        let header_len = pos + 2;
+        let rest = &input[header_len..];
+
+        if rest.len() < len + 2 {
+            return Err(RespParseError::Incomplete);
+        }
+        if len < 4 || rest[3] != b':' {
+            return Err(RespParseError::InvalidFormat);
         }
+        if &rest[len..len + 2] != b"\r\n" {
+            return Err(RespParseError::InvalidFormat);
+        }
+
+        let format = String::from_utf8(rest[..3].to_vec())
+            .map_err(|_| RespParseError::InvalidFormat)?;
+        let data = rest[4..len].to_vec();
 
-        Ok((RespValue::Array(items), rest))
+        Ok((
+            RespValue::VerbatimString { format, data },
+            header_len + len + 2,
+        ))
     } else {
         Err(RespParseError::Incomplete)
     }
 }
+
+fn parse_map(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if let Some(pos) = find_crlf(input) {
+        let len_str =
+            std::str::from_utf8(&input[..pos]).map_err(|_| RespParseError::InvalidFormat)?;
+        let len: usize = len_str.parse().map_err(|_| RespParseError::InvalidFormat)?;
+        let mut consumed = pos + 2;
+
+        let mut pairs = Vec::with_capacity(len);
+        for _ in 0..len {
+            let (key, used) = parse(&input[consumed..])?;
+            consumed += used;
+            let (val, used) = parse(&input[consumed..])?;
+            consumed += used;
+            pairs.push((key, val));
+        }
+
+        Ok((RespValue::Map(pairs), consumed))
+    } else {
+        Err(RespParseError::Incomplete)
+    }
+}
+
+fn parse_set(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if let Some(pos) = find_crlf(input) {
+        let len_str =
+            std::str::from_utf8(&input[..pos]).map_err(|_| RespParseError::InvalidFormat)?;
+        let len: usize = len_str.parse().map_err(|_| RespParseError::InvalidFormat)?;
+        let mut consumed = pos + 2;
+
+        let mut items = Vec::with_capacity(len);
+        for _ in 0..len {
+            let (val, used) = parse(&input[consumed..])?;
+            items.push(val);
+            consumed += used;
+        }
+
+        Ok((RespValue::Set(items), consumed))
+    } else {
+        Err(RespParseError::Incomplete)
+    }
+}
+
+fn parse_push(input: &[u8]) -> Result<(RespValue, usize), RespParseError> {
+    if let Some(pos) = find_crlf(input) {
+        let len_str =
+            std::str::from_utf8(&input[..pos]).map_err(|_| RespParseError::InvalidFormat)?;
+        let len: usize = len_str.parse().map_err(|_| RespParseError::InvalidFormat)?;
+        let mut consumed = pos + 2;
+
+        let mut items = Vec::with_capacity(len);
+        for _ in 0..len {
+            let (val, used) = parse(&input[consumed..])?;
+            items.push(val);
+            consumed += used;
+        }
+
+        Ok((RespValue::Push(items), consumed))
+    } else {
+        Err(RespParseError::Incomplete)
+    }
+}
+
+/// Serializes `value` as RESP wire bytes, appending them to `out`.
+///
+/// This is the inverse of `parse`: every variant of `RespValue` knows how to
+/// write itself, so callers build replies out of `RespValue` instead of
+/// hand-formatting protocol strings.
+pub fn encode(value: &RespValue, out: &mut Vec<u8>) {
+    match value {
+        RespValue::SimpleString(s) => {
+            out.push(b'+');
+            out.extend_from_slice(s.as_bytes());
+            out.extend_from_slice(b"\r\n");
+        }
+        RespValue::Error(s) => {
+            out.push(b'-');
+            out.extend_from_slice(s.as_bytes());
+            out.extend_from_slice(b"\r\n");
+        }
+        RespValue::Integer(n) => {
+            out.extend_from_slice(format!(":{}\r\n", n).as_bytes());
+        }
+        RespValue::BulkString(None) => {
+            out.extend_from_slice(b"$-1\r\n");
+        }
+        RespValue::BulkString(Some(data)) => {
+            out.extend_from_slice(format!("${}\r\n", data.len()).as_bytes());
+            out.extend_from_slice(data);
+            out.extend_from_slice(b"\r\n");
+        }
+        RespValue::Array(items) => {
+            out.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+            for item in items {
+                encode(item, out);
+            }
+        }
+        RespValue::Null => {
+            out.extend_from_slice(b"_\r\n");
+        }
+        RespValue::Double(d) => {
+            out.extend_from_slice(format!(",{}\r\n", d).as_bytes());
+        }
+        RespValue::Boolean(true) => {
+            out.extend_from_slice(b"#t\r\n");
+        }
+        RespValue::Boolean(false) => {
+            out.extend_from_slice(b"#f\r\n");
+        }
+        RespValue::BigNumber(s) => {
+            out.push(b'(');
+            out.extend_from_slice(s.as_bytes());
+            out.extend_from_slice(b"\r\n");
+        }
+        RespValue::VerbatimString { format, data } => {
+            out.extend_from_slice(format!("={}\r\n", data.len() + 4).as_bytes());
+            out.extend_from_slice(format.as_bytes());
+            out.push(b':');
+            out.extend_from_slice(data);
+            out.extend_from_slice(b"\r\n");
+        }
+        RespValue::Map(pairs) => {
+            out.extend_from_slice(format!("%{}\r\n", pairs.len()).as_bytes());
+            for (key, val) in pairs {
+                encode(key, out);
+                encode(val, out);
+            }
+        }
+        RespValue::Set(items) => {
+            out.extend_from_slice(format!("~{}\r\n", items.len()).as_bytes());
+            for item in items {
+                encode(item, out);
+            }
+        }
+        RespValue::Push(items) => {
+            out.extend_from_slice(format!(">{}\r\n", items.len()).as_bytes());
+            for item in items {
+                encode(item, out);
+            }
+        }
+    }
+}